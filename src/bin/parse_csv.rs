@@ -14,22 +14,49 @@ struct Opt {
 
     #[structopt(short = "s", long = "schema")]
     schema: String,
+
+    /// Decode a binary container back into CSV, instead of encoding CSV into one.
+    #[structopt(short = "d", long = "decode")]
+    decode: bool,
+
+    /// Encode with the streaming `Converter` instead of `parse_csv` +
+    /// `write_container`. Trades the self-describing container format (and
+    /// its bounded `-d`/`--decode` read path) for O(batch size) memory on
+    /// both ends, regardless of input size; incompatible with `--decode`.
+    #[structopt(long = "stream")]
+    stream: bool,
 }
 
 fn main() -> Result<(), Box<Error>> {
     env_logger::init();
 
     let opt = Opt::from_args();
-
-    let csv = fs::read(&opt.input)?;
     let schema = kobuta::schema::parse(&opt.schema)?;
-    let mut output = vec![0; 5 * 1024 * 1024];
 
-    kobuta::parse_csv(csv.as_slice(), &schema, &mut output)?;
-
-    fs::write(opt.output, &output)?;
+    if opt.decode && opt.stream {
+        return Err("--decode and --stream are incompatible".into());
+    }
+
+    if opt.decode {
+        let container = fs::read(&opt.input)?;
+        let mut output = fs::File::create(&opt.output)?;
+        kobuta::write_csv(&container, &schema, &mut output)?;
+    } else if opt.stream {
+        let input = fs::File::open(&opt.input)?;
+        let mut output = fs::File::create(&opt.output)?;
+        kobuta::convert::Converter::new(&schema).convert(input, &mut output)?;
+    } else {
+        let csv = fs::read(&opt.input)?;
+        kobuta::validate(&schema, &csv, None)?;
+        let rows = kobuta::parse_csv(csv.as_slice(), &schema)?;
+
+        let mut output = Vec::new();
+        kobuta::container::write_container(&schema, &rows, &mut output)?;
+
+        fs::write(opt.output, &output)?;
+    }
 
     println!("Done.");
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,353 @@
+use std::convert::TryInto;
+use std::str;
+
+use super::schema::{Column, Data, DataType};
+use super::KbtError;
+
+/// Identifies a Kobuta binary container. Any file not starting with these
+/// four bytes is rejected up front rather than mis-parsed.
+pub const MAGIC: &[u8; 4] = b"KBTA";
+
+/// The container format version written by this build.
+pub const VERSION: u16 = 1;
+
+/// Serializes `rows` (one `Option<Data>` per column, `None` meaning null)
+/// into the self-describing container layout: magic, version, the schema
+/// (dtype tag + nullable flag per column), a row count per column, and then
+/// for each column in turn an optional validity bitmap followed by its
+/// data. Fixed-width columns pack their values back to back, null slots
+/// zero-filled so `row_index * dtype.size()` still locates every value.
+/// `Utf8` columns instead write a `row_count + 1`-long `u32` offsets array
+/// followed by the concatenated UTF-8 bytes of every row (nulls contribute
+/// a zero-length span).
+pub fn write_container(
+    schema: &[Column],
+    rows: &[Vec<Option<Data>>],
+    output: &mut Vec<u8>,
+) -> Result<(), KbtError> {
+    output.extend_from_slice(MAGIC);
+    output.extend_from_slice(&VERSION.to_le_bytes());
+    output.extend_from_slice(&(schema.len() as u16).to_le_bytes());
+
+    for column in schema {
+        output.push(column.dtype.tag());
+        output.push(column.nullable as u8);
+    }
+
+    let row_count = rows.len() as u32;
+    for _ in schema {
+        output.extend_from_slice(&row_count.to_le_bytes());
+    }
+
+    for (index, column) in schema.iter().enumerate() {
+        if column.nullable {
+            let mut bitmap = vec![0u8; rows.len().div_ceil(8)];
+            for (row_index, row) in rows.iter().enumerate() {
+                if row.get(index).and_then(|cell| cell.clone()).is_some() {
+                    bitmap[row_index / 8] |= 1 << (row_index % 8);
+                }
+            }
+            output.extend_from_slice(&bitmap);
+        }
+
+        match column.dtype {
+            DataType::Utf8 => write_utf8_column(rows, index, output),
+            _ => write_fixed_column(rows, index, column.dtype, output),
+        }
+    }
+
+    Ok(())
+}
+
+fn write_fixed_column(rows: &[Vec<Option<Data>>], index: usize, dtype: DataType, output: &mut Vec<u8>) {
+    let width = dtype.size().expect("fixed-width dtype") as usize;
+    for row in rows {
+        match row.get(index).and_then(|cell| cell.clone()) {
+            Some(data) => output.extend_from_slice(&data.to_le_bytes()),
+            None => output.extend(std::iter::repeat_n(0u8, width)),
+        }
+    }
+}
+
+fn write_utf8_column(rows: &[Vec<Option<Data>>], index: usize, output: &mut Vec<u8>) {
+    let mut offsets = Vec::with_capacity(rows.len() + 1);
+    let mut bytes = Vec::new();
+    let mut offset = 0u32;
+
+    offsets.push(offset);
+    for row in rows {
+        if let Some(Data::Utf8(value)) = row.get(index).and_then(|cell| cell.clone()) {
+            bytes.extend_from_slice(value.as_bytes());
+            offset += value.len() as u32;
+        }
+        offsets.push(offset);
+    }
+
+    for offset in &offsets {
+        output.extend_from_slice(&offset.to_le_bytes());
+    }
+    output.extend_from_slice(&bytes);
+}
+
+enum ColumnLayout {
+    Fixed { values_offset: usize, width: usize },
+    Utf8 { offsets_offset: usize, bytes_offset: usize },
+}
+
+struct ColumnRegion {
+    dtype: DataType,
+    nullable: bool,
+    row_count: usize,
+    bitmap_offset: usize,
+    layout: ColumnLayout,
+}
+
+/// Random access into a container's packed column regions, returned by
+/// `read_container` alongside the schema it was written with.
+pub struct ColumnReaders<'r> {
+    bytes: &'r [u8],
+    regions: Vec<ColumnRegion>,
+}
+
+impl<'r> ColumnReaders<'r> {
+    pub fn row_count(&self, column: usize) -> usize {
+        self.regions[column].row_count
+    }
+
+    pub fn is_valid(&self, column: usize, row: usize) -> bool {
+        let region = &self.regions[column];
+        if !region.nullable {
+            return true;
+        }
+        let byte = self.bytes[region.bitmap_offset + row / 8];
+        byte & (1 << (row % 8)) != 0
+    }
+
+    /// Zero-copy access to a `Utf8` column's value. `None` for a null slot
+    /// or a non-`Utf8` column.
+    pub fn get_str(&self, column: usize, row: usize) -> Option<&'r str> {
+        let region = &self.regions[column];
+        if !self.is_valid(column, row) {
+            return None;
+        }
+
+        match region.layout {
+            ColumnLayout::Utf8 { offsets_offset, bytes_offset } => {
+                let start = self.read_offset(offsets_offset, row);
+                let end = self.read_offset(offsets_offset, row + 1);
+                str::from_utf8(&self.bytes[bytes_offset + start..bytes_offset + end]).ok()
+            }
+            ColumnLayout::Fixed { .. } => None,
+        }
+    }
+
+    fn read_offset(&self, offsets_offset: usize, row: usize) -> usize {
+        let start = offsets_offset + row * 4;
+        u32::from_le_bytes(self.bytes[start..start + 4].try_into().unwrap()) as usize
+    }
+
+    pub fn get(&self, column: usize, row: usize) -> Option<Data> {
+        let region = &self.regions[column];
+        if !self.is_valid(column, row) {
+            return None;
+        }
+
+        match region.layout {
+            ColumnLayout::Fixed { values_offset, width } => {
+                let start = values_offset + row * width;
+                Some(region.dtype.read_le_bytes(&self.bytes[start..start + width]))
+            }
+            ColumnLayout::Utf8 { .. } => self.get_str(column, row).map(|value| Data::Utf8(value.to_string())),
+        }
+    }
+}
+
+/// Parses a container written by `write_container` back into its schema and
+/// a `ColumnReaders` handle for random access into the packed data.
+pub fn read_container(bytes: &[u8]) -> Result<(Vec<Column>, ColumnReaders<'_>), KbtError> {
+    if bytes.len() < 8 || &bytes[0..4] != MAGIC {
+        return Err(KbtError::container("missing or corrupt magic header", 0));
+    }
+
+    let version = u16::from_le_bytes(bytes[4..6].try_into().unwrap());
+    if version != VERSION {
+        return Err(KbtError::container("unsupported container version", 4));
+    }
+
+    let column_count = u16::from_le_bytes(bytes[6..8].try_into().unwrap()) as usize;
+    let mut pos = 8;
+
+    let mut schema = Vec::with_capacity(column_count);
+    for _ in 0..column_count {
+        let tag = *bytes
+            .get(pos)
+            .ok_or_else(|| KbtError::container("schema truncated", pos))?;
+        let nullable = *bytes
+            .get(pos + 1)
+            .ok_or_else(|| KbtError::container("schema truncated", pos + 1))?
+            != 0;
+        let dtype = DataType::from_tag(tag)
+            .ok_or_else(|| KbtError::container("unknown column type tag", pos))?;
+        schema.push(Column { dtype, nullable });
+        pos += 2;
+    }
+
+    let mut row_counts = Vec::with_capacity(column_count);
+    for _ in 0..column_count {
+        let field = bytes
+            .get(pos..pos + 4)
+            .ok_or_else(|| KbtError::container("row counts truncated", pos))?;
+        row_counts.push(u32::from_le_bytes(field.try_into().unwrap()) as usize);
+        pos += 4;
+    }
+
+    let mut regions = Vec::with_capacity(column_count);
+    for (column, row_count) in schema.iter().zip(row_counts) {
+        let bitmap_offset = pos;
+        if column.nullable {
+            pos += row_count.div_ceil(8);
+        }
+
+        let layout = match column.dtype {
+            DataType::Utf8 => {
+                let offsets_offset = pos;
+                let offsets_len = (row_count + 1) * 4;
+                pos += offsets_len;
+                if pos > bytes.len() {
+                    return Err(KbtError::container("offsets array runs past end of buffer", pos));
+                }
+
+                // Every `get_str`/`get` call trusts these offsets to slice
+                // `bytes` without re-checking bounds, so a corrupt,
+                // non-monotonic array (which would otherwise only surface
+                // as a panic on first access) is rejected up front here.
+                let mut previous = 0u32;
+                for row in 0..=row_count {
+                    let offset_pos = offsets_offset + row * 4;
+                    let offset = u32::from_le_bytes(bytes[offset_pos..offset_pos + 4].try_into().unwrap());
+                    if row > 0 && offset < previous {
+                        return Err(KbtError::container("Utf8 offsets array is not monotonically increasing", offset_pos));
+                    }
+                    previous = offset;
+                }
+
+                let bytes_offset = pos;
+                let last_offset = previous as usize;
+                pos += last_offset;
+                ColumnLayout::Utf8 { offsets_offset, bytes_offset }
+            }
+            _ => {
+                let width = column.dtype.size().expect("fixed-width dtype") as usize;
+                let values_offset = pos;
+                pos += row_count * width;
+                ColumnLayout::Fixed { values_offset, width }
+            }
+        };
+
+        if pos > bytes.len() {
+            return Err(KbtError::container("column data runs past end of buffer", pos));
+        }
+
+        regions.push(ColumnRegion {
+            dtype: column.dtype,
+            nullable: column.nullable,
+            row_count,
+            bitmap_offset,
+            layout,
+        });
+    }
+
+    Ok((schema, ColumnReaders { bytes, regions }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trips_every_fixed_width_dtype() {
+        let schema = [
+            Column { dtype: DataType::Int8, nullable: false },
+            Column { dtype: DataType::Int16, nullable: false },
+            Column { dtype: DataType::Int32, nullable: false },
+            Column { dtype: DataType::Int64, nullable: false },
+            Column { dtype: DataType::Float32, nullable: false },
+            Column { dtype: DataType::Float64, nullable: false },
+            Column { dtype: DataType::Bool, nullable: false },
+        ];
+        let rows = vec![vec![
+            Some(Data::Int8(-1)),
+            Some(Data::Int16(2)),
+            Some(Data::Int32(-3)),
+            Some(Data::Int64(4)),
+            Some(Data::Float32(1.5)),
+            Some(Data::Float64(2.5)),
+            Some(Data::Bool(true)),
+        ]];
+
+        let mut bytes = Vec::new();
+        write_container(&schema, &rows, &mut bytes).unwrap();
+        let (read_schema, readers) = read_container(&bytes).unwrap();
+
+        assert_eq!(schema.to_vec(), read_schema);
+        for (column_index, expected) in rows[0].iter().enumerate() {
+            assert_eq!(expected.clone(), readers.get(column_index, 0));
+        }
+    }
+
+    #[test]
+    fn test_round_trips_nullable_column_validity() {
+        let schema = [Column { dtype: DataType::Int32, nullable: true }];
+        let rows = vec![
+            vec![Some(Data::Int32(1))],
+            vec![None],
+            vec![Some(Data::Int32(3))],
+        ];
+
+        let mut bytes = Vec::new();
+        write_container(&schema, &rows, &mut bytes).unwrap();
+        let (_, readers) = read_container(&bytes).unwrap();
+
+        assert_eq!(Some(Data::Int32(1)), readers.get(0, 0));
+        assert_eq!(None, readers.get(0, 1));
+        assert_eq!(Some(Data::Int32(3)), readers.get(0, 2));
+        assert!(readers.is_valid(0, 0));
+        assert!(!readers.is_valid(0, 1));
+    }
+
+    #[test]
+    fn test_round_trips_utf8_column_including_nulls() {
+        let schema = [Column { dtype: DataType::Utf8, nullable: true }];
+        let rows = vec![
+            vec![Some(Data::Utf8("hello".to_string()))],
+            vec![None],
+            vec![Some(Data::Utf8("".to_string()))],
+        ];
+
+        let mut bytes = Vec::new();
+        write_container(&schema, &rows, &mut bytes).unwrap();
+        let (_, readers) = read_container(&bytes).unwrap();
+
+        assert_eq!(Some("hello"), readers.get_str(0, 0));
+        assert_eq!(None, readers.get_str(0, 1));
+        assert_eq!(Some(""), readers.get_str(0, 2));
+        assert_eq!(Some(Data::Utf8("hello".to_string())), readers.get(0, 0));
+    }
+
+    #[test]
+    fn test_read_container_rejects_non_monotonic_utf8_offsets() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.push(DataType::Utf8.tag());
+        bytes.push(0); // not nullable
+        bytes.extend_from_slice(&2u32.to_le_bytes()); // row_count
+        // Offsets array: row 0 claims bytes [5..0), which is backwards.
+        bytes.extend_from_slice(&5u32.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+
+        assert!(read_container(&bytes).is_err(), "corrupt offsets array should be rejected, not panic later");
+    }
+}
@@ -0,0 +1,215 @@
+use std::error::Error;
+use std::io::{Read, Write};
+
+use super::csv;
+use super::schema::{Column, Data};
+use super::KbtError;
+
+/// The most bytes any fixed-width `Parse::write` impl can produce for one
+/// field (a sign, up to 17 significant `dtoa` digits, and slack). `Utf8`
+/// isn't fixed-width, so its fields are sized by the actual string length
+/// instead (see `field_capacity`).
+const MAX_FIELD_WIDTH: usize = 24;
+
+const DEFAULT_BATCH_ROWS: usize = 1024;
+
+/// How many bytes of CSV `Converter::convert` pulls from its `Read` at a
+/// time. Input is never buffered in full: a read that doesn't end on a
+/// record boundary has its trailing partial record carried over and
+/// prefixed onto the next read instead.
+const READ_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Converts CSV into Kobuta's flat `Parse::write` encoding over a plain
+/// `Read`/`Write` pair, one CSV record at a time, flushing every
+/// `batch_rows` rows. The read side stays O(chunk size) regardless of input
+/// size, unlike `parse_csv`, which collects every row (and
+/// `write_container`, which collects the whole encoded output) into memory
+/// before anything is written. The write side's buffer is sized for
+/// `batch_rows` of fixed-width fields up front and grows only as far as a
+/// batch's actual `Utf8` values require.
+///
+/// This is a separate encoding from `container::write_container`'s
+/// self-describing format: the container needs every row's validity
+/// bitmap and a row count up front, which is fundamentally at odds with
+/// streaming, so a `Converter`-encoded file isn't container data and
+/// can't be read back with `write_csv`/`--decode`. Fields are written back
+/// to back with no separator or length prefix, so the output does not
+/// record where one value ends and the next begins.
+///
+/// `schema` can't declare any nullable columns: the flat encoding has no
+/// bitmap or other side channel to mark a slot null, so `convert` rejects
+/// such a schema with `KbtError::Unsupported` up front rather than silently
+/// misencoding (or erroring opaquely on) the first null value it meets.
+pub struct Converter<'s> {
+    schema: &'s [Column],
+    batch_rows: usize,
+}
+
+impl<'s> Converter<'s> {
+    pub fn new(schema: &'s [Column]) -> Converter<'s> {
+        Converter {
+            schema,
+            batch_rows: DEFAULT_BATCH_ROWS,
+        }
+    }
+
+    pub fn batch_rows(&mut self, batch_rows: usize) -> &mut Converter<'s> {
+        self.batch_rows = batch_rows;
+        self
+    }
+
+    pub fn convert(&self, mut reader: impl Read, mut writer: impl Write) -> Result<(), Box<Error>> {
+        if self.schema.iter().any(|column| column.nullable) {
+            return Err(Box::new(KbtError::unsupported(
+                "Converter can't stream a nullable column: its flat encoding has no bitmap to mark a slot null",
+            )));
+        }
+
+        let row_width = self.schema.len() * MAX_FIELD_WIDTH;
+        let mut out_buffer = vec![0u8; row_width * self.batch_rows.max(1)];
+        let mut cursor = 0;
+        let mut rows_buffered = 0;
+        let mut record_index = 0;
+
+        let builder = csv::ReaderBuilder::new();
+        let mut carry = Vec::new();
+        let mut chunk = vec![0u8; READ_CHUNK_BYTES];
+
+        loop {
+            let read = reader.read(&mut chunk)?;
+            if read > 0 {
+                carry.extend_from_slice(&chunk[..read]);
+            }
+
+            // On EOF there's nothing left to wait for: whatever remains in
+            // `carry`, terminated or not, is the final record.
+            let boundary = if read == 0 { carry.len() } else { builder.complete_prefix_len(&carry) };
+
+            if boundary > 0 {
+                let complete: Vec<u8> = carry.drain(..boundary).collect();
+
+                for record in builder.from_bytes(&complete) {
+                    let record = record?;
+                    record_index += 1;
+
+                    for (column_index, (column, field)) in self.schema.iter().zip(record.iter()).enumerate() {
+                        let data = column.parse_data(&field).map_err(|_| {
+                            let offset = record.field_offset(column_index).unwrap_or(0);
+                            KbtError::csv_field(&complete, record_index, column_index, column.dtype, &field, offset)
+                        })?;
+
+                        let needed = field_capacity(&data);
+                        if out_buffer.len() - cursor < needed {
+                            out_buffer.resize(cursor + needed, 0);
+                        }
+
+                        let available = out_buffer.len() - cursor;
+                        let remainder = data.write(&mut out_buffer[cursor..])?;
+                        cursor += available - remainder.len();
+                    }
+
+                    rows_buffered += 1;
+                    if rows_buffered >= self.batch_rows {
+                        writer.write_all(&out_buffer[..cursor])?;
+                        cursor = 0;
+                        rows_buffered = 0;
+                    }
+                }
+            }
+
+            if read == 0 {
+                break;
+            }
+        }
+
+        if cursor > 0 {
+            writer.write_all(&out_buffer[..cursor])?;
+        }
+
+        Ok(())
+    }
+}
+
+/// How many bytes `out_buffer` must have free for `data.write` to succeed.
+/// Fixed-width types always fit `MAX_FIELD_WIDTH`; `Utf8` is written
+/// byte-for-byte, so it needs exactly its string length.
+fn field_capacity(data: &Data) -> usize {
+    match data {
+        Data::Utf8(value) => value.len(),
+        _ => MAX_FIELD_WIDTH,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::schema::DataType;
+
+    fn convert(schema: &[Column], csv: &str) -> Vec<u8> {
+        let mut out = Vec::new();
+        Converter::new(schema).convert(csv.as_bytes(), &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_convert_writes_parse_write_encoding() {
+        let schema = [
+            Column { dtype: DataType::Int32, nullable: false },
+            Column { dtype: DataType::Bool, nullable: false },
+        ];
+
+        assert_eq!(b"1true2false".to_vec(), convert(&schema, "1,true\n2,false\n"));
+    }
+
+    #[test]
+    fn test_convert_batches_without_losing_rows() {
+        let schema = [Column { dtype: DataType::Int32, nullable: false }];
+        let mut converter = Converter::new(&schema);
+        converter.batch_rows(2);
+
+        let mut out = Vec::new();
+        converter.convert("1\n2\n3\n".as_bytes(), &mut out).unwrap();
+        assert_eq!(b"123".to_vec(), out);
+    }
+
+    #[test]
+    fn test_convert_grows_buffer_for_utf8_fields_past_max_field_width() {
+        let schema = [Column { dtype: DataType::Utf8, nullable: false }];
+        let mut converter = Converter::new(&schema);
+        converter.batch_rows(2);
+
+        let long_value = "x".repeat(MAX_FIELD_WIDTH * 3);
+        let csv = format!("{}\n{}\n", long_value, long_value);
+
+        let mut expected = long_value.clone().into_bytes();
+        expected.extend_from_slice(long_value.as_bytes());
+
+        let mut out = Vec::new();
+        converter.convert(csv.as_bytes(), &mut out).unwrap();
+        assert_eq!(expected, out);
+    }
+
+    #[test]
+    fn test_convert_rejects_nullable_schema() {
+        let schema = [Column { dtype: DataType::Int32, nullable: true }];
+        let mut out = Vec::new();
+
+        assert!(Converter::new(&schema).convert("12\n\n34\n".as_bytes(), &mut out).is_err());
+    }
+
+    #[test]
+    fn test_convert_handles_quoted_field_spanning_a_read_chunk_boundary() {
+        let schema = [Column { dtype: DataType::Utf8, nullable: false }];
+        // Padded so the opening quote falls just before the first
+        // `READ_CHUNK_BYTES`-sized read ends, and the embedded newline plus
+        // closing quote only show up in the second `read` call.
+        let padding_len = READ_CHUNK_BYTES - 2;
+        let padding = "0\n".repeat(padding_len / 2);
+        let csv = format!("{}\"a\nb\"\n", padding);
+
+        let mut expected = "0".repeat(padding_len / 2).into_bytes();
+        expected.extend_from_slice(b"a\nb");
+
+        assert_eq!(expected, convert(&schema, &csv));
+    }
+}
@@ -0,0 +1,416 @@
+use std::borrow::Cow;
+
+use super::KbtError;
+
+/// Controls which parts of a record get surrounding whitespace stripped
+/// before the field bytes are handed off to the caller.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trim {
+    None,
+    Headers,
+    Fields,
+    All,
+}
+
+impl Trim {
+    fn trims_headers(&self) -> bool {
+        matches!(self, Trim::Headers | Trim::All)
+    }
+
+    fn trims_fields(&self) -> bool {
+        matches!(self, Trim::Fields | Trim::All)
+    }
+}
+
+/// Configures an RFC 4180 `Reader`: delimiter, quote and terminator bytes,
+/// whether the first record is a header to skip, and a `Trim` mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReaderBuilder {
+    delimiter: u8,
+    quote: u8,
+    terminator: u8,
+    has_headers: bool,
+    trim: Trim,
+}
+
+impl Default for ReaderBuilder {
+    fn default() -> ReaderBuilder {
+        ReaderBuilder::new()
+    }
+}
+
+impl ReaderBuilder {
+    pub fn new() -> ReaderBuilder {
+        ReaderBuilder {
+            delimiter: b',',
+            quote: b'"',
+            terminator: b'\n',
+            has_headers: false,
+            trim: Trim::None,
+        }
+    }
+
+    pub fn delimiter(&mut self, delimiter: u8) -> &mut ReaderBuilder {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn quote(&mut self, quote: u8) -> &mut ReaderBuilder {
+        self.quote = quote;
+        self
+    }
+
+    pub fn terminator(&mut self, terminator: u8) -> &mut ReaderBuilder {
+        self.terminator = terminator;
+        self
+    }
+
+    pub fn has_headers(&mut self, yes: bool) -> &mut ReaderBuilder {
+        self.has_headers = yes;
+        self
+    }
+
+    pub fn trim(&mut self, trim: Trim) -> &mut ReaderBuilder {
+        self.trim = trim;
+        self
+    }
+
+    pub fn from_bytes<'r>(&self, input: &'r [u8]) -> Reader<'r> {
+        Reader {
+            input,
+            builder: *self,
+            pos: 0,
+            header_skipped: !self.has_headers,
+            headers: None,
+        }
+    }
+
+    /// The length of the longest prefix of `buf` that ends on a complete
+    /// record boundary (a `terminator` byte outside of a quoted field), or
+    /// `0` if `buf` doesn't contain one yet. A streaming caller that only
+    /// has part of the input can feed whatever it has so far in here,
+    /// parse the returned prefix, and carry the rest over to the next read
+    /// — without risking a chunk boundary landing inside a quoted field
+    /// that itself contains the terminator byte.
+    pub(crate) fn complete_prefix_len(&self, buf: &[u8]) -> usize {
+        let mut in_quotes = false;
+        let mut field_start = 0;
+        let mut boundary = 0;
+        let mut i = 0;
+
+        while i < buf.len() {
+            let byte = buf[i];
+
+            if in_quotes {
+                if byte == self.quote {
+                    if buf.get(i + 1) == Some(&self.quote) {
+                        i += 2;
+                        continue;
+                    }
+                    in_quotes = false;
+                }
+                i += 1;
+                continue;
+            }
+
+            if byte == self.quote && i == field_start {
+                in_quotes = true;
+                i += 1;
+                continue;
+            }
+
+            if byte == self.delimiter {
+                i += 1;
+                field_start = i;
+                continue;
+            }
+
+            if byte == self.terminator {
+                i += 1;
+                field_start = i;
+                boundary = i;
+                continue;
+            }
+
+            i += 1;
+        }
+
+        boundary
+    }
+}
+
+/// The byte range to record for one field. Quoted fields are anchored on
+/// the quote bytes themselves (`quote_start`..`quoted_end`, the latter
+/// falling back to `end` only if closing never updated it), discarding any
+/// leading/trailing whitespace `trim_fields` skipped past; unquoted fields
+/// keep their full untrimmed span so `Record::get`'s own `.trim()` applies.
+fn field_span(
+    field_start: usize,
+    quote_start: usize,
+    quoted: bool,
+    quoted_end: Option<usize>,
+    end: usize,
+) -> (usize, usize, bool) {
+    if quoted {
+        (quote_start, quoted_end.unwrap_or(end), true)
+    } else {
+        (field_start, end, false)
+    }
+}
+
+/// A single record: the field byte-ranges into the `Reader`'s input, plus
+/// whether each field was quoted. Unquoted fields are handed back as a
+/// borrow straight into the original buffer; a quoted field that contains a
+/// `""` escape has to be unescaped into an owned `String` instead.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Record<'r> {
+    input: &'r [u8],
+    quote: u8,
+    fields: Vec<(usize, usize, bool)>,
+    trim_fields: bool,
+}
+
+impl<'r> Record<'r> {
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// The byte offset of field `i` into the `Reader`'s original input.
+    pub fn field_offset(&self, i: usize) -> Option<usize> {
+        self.fields.get(i).map(|&(start, _, _)| start)
+    }
+
+    pub fn get(&self, i: usize) -> Option<Cow<'r, str>> {
+        let &(start, end, quoted) = self.fields.get(i)?;
+        let slice = &self.input[start..end];
+
+        let field: Cow<'r, str> = if quoted {
+            // `slice` spans the opening and closing quote; strip both, then
+            // collapse any `""` escape left inside.
+            let inner = &slice[1..slice.len().saturating_sub(1)];
+            if inner.contains(&self.quote) {
+                let mut unescaped = Vec::with_capacity(inner.len());
+                let mut bytes = inner.iter().copied().peekable();
+                while let Some(byte) = bytes.next() {
+                    unescaped.push(byte);
+                    if byte == self.quote && bytes.peek() == Some(&self.quote) {
+                        bytes.next();
+                    }
+                }
+                Cow::Owned(String::from_utf8(unescaped).unwrap_or_default())
+            } else {
+                Cow::Borrowed(std::str::from_utf8(inner).unwrap_or(""))
+            }
+        } else {
+            Cow::Borrowed(std::str::from_utf8(slice).unwrap_or(""))
+        };
+
+        Some(if self.trim_fields {
+            match field {
+                Cow::Borrowed(s) => Cow::Borrowed(s.trim()),
+                Cow::Owned(s) => Cow::Owned(s.trim().to_string()),
+            }
+        } else {
+            field
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Cow<'r, str>> + '_ {
+        (0..self.len()).map(move |i| self.get(i).unwrap())
+    }
+}
+
+/// Iterates RFC 4180 records out of a byte slice: quoted fields with `""`
+/// escaping, delimiters and terminators embedded inside quotes, and an
+/// optional header row skipped up front.
+pub struct Reader<'r> {
+    input: &'r [u8],
+    builder: ReaderBuilder,
+    pos: usize,
+    header_skipped: bool,
+    headers: Option<Record<'r>>,
+}
+
+impl<'r> Reader<'r> {
+    /// The header record, if `has_headers` was set and a first record has
+    /// been read.
+    pub fn headers(&self) -> Option<&Record<'r>> {
+        self.headers.as_ref()
+    }
+
+    fn read_record(&mut self, trim_fields: bool) -> Option<Result<Record<'r>, KbtError>> {
+        if self.pos >= self.input.len() {
+            return None;
+        }
+
+        let mut fields = Vec::new();
+        let mut field_start = self.pos;
+        // Where an opening quote is still recognized. Equal to `field_start`
+        // unless `trim_fields` is set, in which case leading spaces/tabs
+        // advance it past themselves so `"..."` is still seen as quoted
+        // even when whitespace precedes it, matching mature CSV tooling.
+        let mut quote_start = self.pos;
+        let mut field_quoted = false;
+        let mut quoted_end = None;
+        let mut in_quotes = false;
+        let mut i = self.pos;
+
+        while i < self.input.len() {
+            let byte = self.input[i];
+
+            if in_quotes {
+                if byte == self.builder.quote {
+                    if self.input.get(i + 1) == Some(&self.builder.quote) {
+                        // Escaped quote: skip both bytes and stay in quotes.
+                        i += 2;
+                        continue;
+                    }
+                    in_quotes = false;
+                    quoted_end = Some(i + 1);
+                }
+                i += 1;
+                continue;
+            }
+
+            if byte == self.builder.quote && i == quote_start {
+                in_quotes = true;
+                field_quoted = true;
+                i += 1;
+                continue;
+            }
+
+            if trim_fields && !field_quoted && i == quote_start && (byte == b' ' || byte == b'\t') {
+                quote_start += 1;
+                i += 1;
+                continue;
+            }
+
+            if byte == self.builder.delimiter {
+                fields.push(field_span(field_start, quote_start, field_quoted, quoted_end, i));
+                i += 1;
+                field_start = i;
+                quote_start = i;
+                field_quoted = false;
+                quoted_end = None;
+                continue;
+            }
+
+            if byte == self.builder.terminator {
+                fields.push(field_span(field_start, quote_start, field_quoted, quoted_end, i));
+                self.pos = i + 1;
+                return Some(Ok(self.finish_record(fields, trim_fields)));
+            }
+
+            i += 1;
+        }
+
+        if in_quotes {
+            self.pos = self.input.len();
+            return Some(Err(KbtError::csv_syntax(self.input, quote_start)));
+        }
+
+        fields.push(field_span(field_start, quote_start, field_quoted, quoted_end, i));
+        self.pos = i;
+        Some(Ok(self.finish_record(fields, trim_fields)))
+    }
+
+    fn finish_record(&self, fields: Vec<(usize, usize, bool)>, trim_fields: bool) -> Record<'r> {
+        Record {
+            input: self.input,
+            quote: self.builder.quote,
+            fields,
+            trim_fields,
+        }
+    }
+}
+
+impl<'r> Iterator for Reader<'r> {
+    type Item = Result<Record<'r>, KbtError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.header_skipped {
+            self.header_skipped = true;
+            match self.read_record(self.builder.trim.trims_headers()) {
+                Some(Ok(record)) => self.headers = Some(record),
+                Some(Err(err)) => return Some(Err(err)),
+                None => return None,
+            }
+        }
+
+        self.read_record(self.builder.trim.trims_fields())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(input: &str, builder: &ReaderBuilder) -> Vec<Vec<String>> {
+        builder
+            .from_bytes(input.as_bytes())
+            .map(|record| record.unwrap().iter().map(|field| field.into_owned()).collect())
+            .collect()
+    }
+
+    const VALID_RECORDS: &[(&str, &[&[&str]])] = &[
+        ("123,4.5\n", &[&["123", "4.5"]]),
+        ("\"123\",\"4.5\"\n", &[&["123", "4.5"]]),
+        ("\"a, b\",\"c\nd\"\n", &[&["a, b", "c\nd"]]),
+        ("\"say \"\"hi\"\"\"\n", &[&["say \"hi\""]]),
+        ("a,b\n1,2\n", &[&["a", "b"], &["1", "2"]]),
+        ("a,b", &[&["a", "b"]]),
+    ];
+
+    #[test]
+    fn test_quoting_and_escaping() {
+        let builder = ReaderBuilder::new();
+        for (input, expected) in VALID_RECORDS {
+            let actual = records(input, &builder);
+            let expected: Vec<Vec<String>> = expected
+                .iter()
+                .map(|row| row.iter().map(|field| field.to_string()).collect())
+                .collect();
+            assert_eq!(expected, actual, "input {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_trim_fields() {
+        let mut builder = ReaderBuilder::new();
+        builder.trim(Trim::Fields);
+        assert_eq!(vec![vec!["a".to_string(), "b".to_string()]], records(" a , \"b\" \n", &builder));
+    }
+
+    #[test]
+    fn test_has_headers_is_skipped_but_recorded() {
+        let mut builder = ReaderBuilder::new();
+        builder.has_headers(true);
+        let input = b"name,age\nAda,36\n";
+        let mut reader = builder.from_bytes(input);
+        let record = reader.next().unwrap().unwrap();
+        assert_eq!("Ada", record.get(0).unwrap().as_ref());
+        assert_eq!("name", reader.headers().unwrap().get(0).unwrap().as_ref());
+    }
+
+    #[test]
+    fn test_complete_prefix_len_stops_outside_quotes() {
+        let builder = ReaderBuilder::new();
+        assert_eq!(0, builder.complete_prefix_len(b"a,b"));
+        assert_eq!(4, builder.complete_prefix_len(b"a,b\n"));
+        assert_eq!(4, builder.complete_prefix_len(b"a,b\nc,"));
+        // The only `\n` is inside a quoted field, so nothing is complete yet.
+        assert_eq!(0, builder.complete_prefix_len(b"\"a\nb\","));
+        assert_eq!(8, builder.complete_prefix_len(b"\"a\nb\",c\n"));
+    }
+
+    #[test]
+    fn test_unterminated_quote_is_an_error() {
+        let builder = ReaderBuilder::new();
+        let mut reader = builder.from_bytes(b"\"unterminated\n");
+        assert!(reader.next().unwrap().is_err());
+    }
+}
@@ -2,15 +2,116 @@ extern crate log;
 
 use std::error::Error;
 use std::fmt;
+use std::io;
 
+pub mod container;
+pub mod convert;
+pub mod csv;
 pub mod schema;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub struct KbtError;
+use schema::{Column, Data, DataType, Parse};
+
+/// A parse failure with enough context to point at exactly where it
+/// happened, rendered by `Display` as a one-line message plus a
+/// rustc-style snippet with a caret under the offending span.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KbtError {
+    /// The schema grammar failed at `offset` into `source`.
+    Schema {
+        source: String,
+        offset: usize,
+        expected: &'static str,
+    },
+    /// A CSV record had an unterminated quoted field starting at `offset`.
+    CsvSyntax { source: String, offset: usize },
+    /// Field `column` of 1-based CSV record `record` didn't parse as the
+    /// schema's declared `dtype`.
+    CsvField {
+        source: String,
+        record: usize,
+        column: usize,
+        dtype: DataType,
+        field: String,
+        offset: usize,
+    },
+    /// A binary container was malformed: bad magic/version, an unknown type
+    /// tag, or a region running past the end of the buffer.
+    Container { reason: &'static str, offset: usize },
+    /// `validate` found one or more structural or type mismatches between a
+    /// schema and the CSV it's meant to describe. Every mismatch found is
+    /// listed, not just the first.
+    Validation { issues: Vec<String> },
+    /// A schema or operation isn't supported by the code path it was handed
+    /// to, e.g. `convert::Converter` asked to stream a nullable column.
+    Unsupported { reason: &'static str },
+}
+
+impl KbtError {
+    pub(crate) fn schema(source: &str, at: &str, expected: &'static str) -> KbtError {
+        KbtError::Schema {
+            source: source.to_string(),
+            // `at` is a subslice of `source`, but not necessarily a suffix:
+            // `schema::parse` trims trailing whitespace before parsing.
+            offset: at.as_ptr() as usize - source.as_ptr() as usize,
+            expected,
+        }
+    }
+
+    pub(crate) fn csv_syntax(source: &[u8], offset: usize) -> KbtError {
+        KbtError::CsvSyntax {
+            source: String::from_utf8_lossy(source).into_owned(),
+            offset,
+        }
+    }
+
+    pub(crate) fn csv_field(
+        source: &[u8],
+        record: usize,
+        column: usize,
+        dtype: DataType,
+        field: &str,
+        offset: usize,
+    ) -> KbtError {
+        KbtError::CsvField {
+            source: String::from_utf8_lossy(source).into_owned(),
+            record,
+            column,
+            dtype,
+            field: field.to_string(),
+            offset,
+        }
+    }
+
+    pub(crate) fn container(reason: &'static str, offset: usize) -> KbtError {
+        KbtError::Container { reason, offset }
+    }
+
+    pub(crate) fn unsupported(reason: &'static str) -> KbtError {
+        KbtError::Unsupported { reason }
+    }
+
+    fn source_and_offset(&self) -> Option<(&str, usize)> {
+        match self {
+            KbtError::Schema { source, offset, .. } => Some((source, *offset)),
+            KbtError::CsvSyntax { source, offset } => Some((source, *offset)),
+            KbtError::CsvField { source, offset, .. } => Some((source, *offset)),
+            KbtError::Container { .. } => None,
+            KbtError::Validation { .. } => None,
+            KbtError::Unsupported { .. } => None,
+        }
+    }
+}
 
 impl Error for KbtError {
     fn description(&self) -> &str {
-        "Kobuta error"
+        match self {
+            KbtError::Schema { .. } => "schema parse error",
+            KbtError::CsvSyntax { .. } => "CSV syntax error",
+            KbtError::CsvField { .. } => "CSV field type mismatch",
+            KbtError::Container { .. } => "malformed container",
+            KbtError::Validation { .. } => "schema validation error",
+            KbtError::Unsupported { .. } => "unsupported operation",
+        }
     }
 
     fn cause(&self) -> Option<&Error> {
@@ -20,6 +121,359 @@ impl Error for KbtError {
 
 impl fmt::Display for KbtError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Kobuta error")
+        match self {
+            KbtError::Schema { expected, .. } => {
+                writeln!(f, "invalid schema: expected {}", expected)?;
+            }
+            KbtError::CsvSyntax { .. } => {
+                writeln!(f, "invalid CSV: unterminated quoted field")?;
+            }
+            KbtError::CsvField {
+                record,
+                column,
+                dtype,
+                field,
+                ..
+            } => {
+                writeln!(
+                    f,
+                    "invalid CSV: record {}, column {}: expected {:?}, found `{}`",
+                    record, column, dtype, field
+                )?;
+            }
+            KbtError::Container { reason, offset } => {
+                return write!(f, "invalid container at byte offset {}: {}", offset, reason);
+            }
+            KbtError::Validation { issues } => {
+                return write!(f, "invalid schema: {}", issues.join("; "));
+            }
+            KbtError::Unsupported { reason } => {
+                return write!(f, "unsupported: {}", reason);
+            }
+        }
+
+        let (source, offset) = self.source_and_offset().expect("non-container variant");
+        write_snippet(f, source, offset)
+    }
+}
+
+/// Renders the line containing `offset` followed by a caret pointing at it,
+/// in the style of a rustc diagnostic.
+fn write_snippet(f: &mut fmt::Formatter, source: &str, offset: usize) -> fmt::Result {
+    let offset = offset.min(source.len());
+    let line_start = source[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let line_end = source[offset..]
+        .find('\n')
+        .map(|i| offset + i)
+        .unwrap_or_else(|| source.len());
+    let line_number = source[..line_start].matches('\n').count() + 1;
+    let column = offset - line_start;
+
+    writeln!(f, "{}", &source[line_start..line_end])?;
+    write!(f, "{}^ line {}, column {}", " ".repeat(column), line_number, column + 1)
+}
+
+/// Parses CSV `input` into rows of `Option<Data>`, one value per `schema`
+/// column (`None` for an empty field in a nullable column), ready to hand to
+/// `container::write_container`.
+pub fn parse_csv(input: &[u8], schema: &[Column]) -> Result<Vec<Vec<Option<Data>>>, KbtError> {
+    let reader = csv::ReaderBuilder::new().from_bytes(input);
+    let mut rows = Vec::new();
+
+    for (record_index, record) in reader.enumerate() {
+        let record = record?;
+        let mut row = Vec::with_capacity(schema.len());
+
+        for (column_index, (column, field)) in schema.iter().zip(record.iter()).enumerate() {
+            let data = if field.is_empty() && column.nullable {
+                None
+            } else {
+                let data = column.parse_data(&field).map_err(|_| {
+                    let offset = record.field_offset(column_index).unwrap_or(0);
+                    KbtError::csv_field(input, record_index + 1, column_index, column.dtype, &field, offset)
+                })?;
+                Some(data)
+            };
+            row.push(data);
+        }
+
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+/// How many of each column's fields `validate` samples before judging
+/// whether its declared type is a good fit.
+const VALIDATION_SAMPLE_ROWS: usize = 32;
+
+/// Checks `schema` against CSV `input` before a real `parse_csv` or
+/// `Converter::convert` run: every record's field count against
+/// `schema.len()`, and a sample of each column's fields against its
+/// declared `DataType`. Every mismatch found is collected into one
+/// `KbtError::Validation` instead of stopping at the first, so a bad
+/// schema or a misaligned CSV can be fixed in a single pass.
+///
+/// `header`, if given, is checked against `schema.len()` and otherwise used
+/// only to name columns in the reported issues (pass the CSV's header
+/// record, split into fields, if it has one); it plays no part in the
+/// per-row checks, which always match columns up by position. `input` is
+/// read with no header row skipped, so its first record is checked like
+/// any other.
+pub fn validate(schema: &[Column], input: &[u8], header: Option<&[&str]>) -> Result<(), KbtError> {
+    let mut issues = Vec::new();
+
+    if let Some(header) = header {
+        if header.len() != schema.len() {
+            issues.push(format!(
+                "header has {} column{} but schema declares {}",
+                header.len(),
+                if header.len() == 1 { "" } else { "s" },
+                schema.len()
+            ));
+        }
+    }
+    let header = header.filter(|header| header.len() == schema.len());
+
+    let mut samples: Vec<Vec<String>> = vec![Vec::new(); schema.len()];
+
+    for (record_index, record) in csv::ReaderBuilder::new().from_bytes(input).enumerate() {
+        let record = match record {
+            Ok(record) => record,
+            Err(_) => continue,
+        };
+
+        if record.len() != schema.len() {
+            issues.push(format!(
+                "row {}: expected {} fields, found {}",
+                record_index + 1,
+                schema.len(),
+                record.len()
+            ));
+            continue;
+        }
+
+        for (column_index, field) in record.iter().enumerate() {
+            if samples[column_index].len() < VALIDATION_SAMPLE_ROWS {
+                samples[column_index].push(field.into_owned());
+            }
+        }
+    }
+
+    for (column_index, column) in schema.iter().enumerate() {
+        let fields = &samples[column_index];
+        let failing: Vec<&str> = fields
+            .iter()
+            .map(|field| field.as_str())
+            .filter(|field| !(field.is_empty() && column.nullable) && column.parse_data(field).is_err())
+            .collect();
+
+        if failing.is_empty() {
+            continue;
+        }
+
+        let label = match header.map(|header| header[column_index]) {
+            Some(name) => format!("column {} ({:?})", column_index, name),
+            None => format!("column {}", column_index),
+        };
+
+        if failing.len() == fields.len() && is_integer(column.dtype) && all_look_like_floats(&failing) {
+            issues.push(format!("{} declared {:?} but all sampled values are floats", label, column.dtype));
+        } else {
+            issues.push(format!("{} declared {:?} but `{}` does not parse", label, column.dtype, failing[0]));
+        }
+    }
+
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(KbtError::Validation { issues })
+    }
+}
+
+fn is_integer(dtype: DataType) -> bool {
+    matches!(dtype, DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64)
+}
+
+fn all_look_like_floats(fields: &[&str]) -> bool {
+    fields.iter().all(|field| f64::parse(field).is_ok())
+}
+
+/// Reverses `parse_csv` + `container::write_container`: reads a binary
+/// `container`, formats each value through `Parse::write`, and emits it as
+/// RFC 4180 CSV (quoting fields that contain the delimiter, a quote, or a
+/// newline; nullable columns emit an empty field for a missing value).
+pub fn write_csv(container: &[u8], schema: &[Column], out: &mut impl io::Write) -> Result<(), Box<Error>> {
+    let (_, readers) = self::container::read_container(container)?;
+    let row_count = schema.iter().enumerate().map(|(i, _)| readers.row_count(i)).next().unwrap_or(0);
+
+    let mut field_buf = [0u8; 32];
+
+    for row in 0..row_count {
+        for (column_index, column) in schema.iter().enumerate() {
+            if column_index > 0 {
+                out.write_all(b",")?;
+            }
+
+            // `Utf8` has no fixed width, so its value is written straight
+            // from the container's zero-copy byte region instead of
+            // through `field_buf`, which is sized only for the other,
+            // fixed-width types.
+            if column.dtype == DataType::Utf8 {
+                if let Some(value) = readers.get_str(column_index, row) {
+                    write_quoted_field(out, value.as_bytes())?;
+                }
+            } else if let Some(data) = readers.get(column_index, row) {
+                let available = field_buf.len();
+                let remainder = data.write(&mut field_buf).map_err(|_| KbtError::container("value does not fit the format buffer", 0))?;
+                let written = available - remainder.len();
+                write_quoted_field(out, &field_buf[..written])?;
+            }
+        }
+        out.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
+
+fn write_quoted_field(out: &mut impl io::Write, field: &[u8]) -> io::Result<()> {
+    let needs_quoting = field.iter().any(|&b| b == b',' || b == b'"' || b == b'\n' || b == b'\r');
+    if !needs_quoting {
+        return out.write_all(field);
+    }
+
+    out.write_all(b"\"")?;
+    for &byte in field {
+        if byte == b'"' {
+            out.write_all(b"\"\"")?;
+        } else {
+            out.write_all(&[byte])?;
+        }
     }
-}
\ No newline at end of file
+    out.write_all(b"\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_error_display_has_snippet_and_caret() {
+        let rendered = schema::parse("Float33").unwrap_err().to_string();
+        assert!(rendered.starts_with("invalid schema: expected a data type"), "{:?}", rendered);
+        assert!(rendered.contains("Float33\n^ line 1, column 1"), "{:?}", rendered);
+    }
+
+    #[test]
+    fn test_csv_syntax_error_display() {
+        let schema = [Column { dtype: DataType::Int32, nullable: false }];
+        let rendered = parse_csv(b"\"unterminated", &schema).unwrap_err().to_string();
+        assert!(rendered.starts_with("invalid CSV: unterminated quoted field\n"), "{:?}", rendered);
+    }
+
+    #[test]
+    fn test_csv_field_error_display() {
+        let schema = [Column { dtype: DataType::Int32, nullable: false }];
+        let rendered = parse_csv(b"abc\n", &schema).unwrap_err().to_string();
+
+        assert_eq!(
+            "invalid CSV: record 1, column 0: expected Int32, found `abc`\nabc\n^ line 1, column 1",
+            rendered
+        );
+    }
+
+    #[test]
+    fn test_container_error_display() {
+        let rendered = KbtError::container("missing or corrupt magic header", 0).to_string();
+        assert_eq!("invalid container at byte offset 0: missing or corrupt magic header", rendered);
+    }
+
+    #[test]
+    fn test_validation_error_display() {
+        let err = KbtError::Validation {
+            issues: vec!["column 0: bad".to_string(), "column 1: also bad".to_string()],
+        };
+        assert_eq!("invalid schema: column 0: bad; column 1: also bad", err.to_string());
+    }
+
+    #[test]
+    fn test_unsupported_error_display() {
+        assert_eq!("unsupported: nope", KbtError::unsupported("nope").to_string());
+    }
+
+    #[test]
+    fn test_write_csv_round_trips_through_parse_csv_and_write_container() {
+        let schema = [
+            Column { dtype: DataType::Int32, nullable: false },
+            Column { dtype: DataType::Utf8, nullable: true },
+        ];
+        let csv = b"1,hello\n2,\n3,\"a, b\"\n";
+
+        let rows = parse_csv(csv, &schema).unwrap();
+        let mut container = Vec::new();
+        self::container::write_container(&schema, &rows, &mut container).unwrap();
+
+        let mut decoded = Vec::new();
+        write_csv(&container, &schema, &mut decoded).unwrap();
+
+        assert_eq!("1,hello\n2,\n3,\"a, b\"\n", String::from_utf8(decoded).unwrap());
+    }
+
+    #[test]
+    fn test_validate_reports_header_column_count_mismatch() {
+        let schema = [Column { dtype: DataType::Int32, nullable: false }];
+        let err = validate(&schema, b"1\n", Some(&["a", "b"])).unwrap_err();
+
+        match err {
+            KbtError::Validation { issues } => {
+                assert_eq!(vec!["header has 2 columns but schema declares 1".to_string()], issues);
+            }
+            _ => panic!("expected Validation, got {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_validate_reports_row_field_count_mismatch() {
+        let schema = [Column { dtype: DataType::Int32, nullable: false }, Column { dtype: DataType::Int32, nullable: false }];
+        let err = validate(&schema, b"1,2\n3\n", None).unwrap_err();
+
+        match err {
+            KbtError::Validation { issues } => {
+                assert_eq!(vec!["row 2: expected 2 fields, found 1".to_string()], issues);
+            }
+            _ => panic!("expected Validation, got {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_validate_reports_column_type_mismatch() {
+        let schema = [Column { dtype: DataType::Int32, nullable: false }];
+        let err = validate(&schema, b"1\nabc\n", Some(&["count"])).unwrap_err();
+
+        match err {
+            KbtError::Validation { issues } => {
+                assert_eq!(vec!["column 0 (\"count\") declared Int32 but `abc` does not parse".to_string()], issues);
+            }
+            _ => panic!("expected Validation, got {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_validate_reports_all_sampled_values_are_floats() {
+        let schema = [Column { dtype: DataType::Int32, nullable: false }];
+        let err = validate(&schema, b"1.5\n2.5\n", None).unwrap_err();
+
+        match err {
+            KbtError::Validation { issues } => {
+                assert_eq!(vec!["column 0 declared Int32 but all sampled values are floats".to_string()], issues);
+            }
+            _ => panic!("expected Validation, got {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_validate_accepts_matching_schema() {
+        let schema = [Column { dtype: DataType::Int32, nullable: false }];
+        assert!(validate(&schema, b"1\n2\n", Some(&["count"])).is_ok());
+    }
+}
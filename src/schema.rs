@@ -1,28 +1,135 @@
 use super::KbtError;
 use std::error::Error;
+use std::fmt;
 use std::str::FromStr;
 use std::mem;
 use log::{trace, info};
 
+/// `Utf8` is the one variable-width type: the container format stores it as
+/// a per-row offsets array into a contiguous byte region rather than a
+/// `size()`-wide slot, so `size()` returns `None` for it.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum DataType {
-    Float32,
+    Int8,
+    Int16,
     Int32,
+    Int64,
+    Float32,
+    Float64,
+    Bool,
+    Utf8,
 }
 
 impl DataType {
-    pub fn size(&self) -> u16 {
+    /// The width, in bytes and little-endian, of one value in the
+    /// container's packed layout. `None` for `Utf8`, which has no fixed
+    /// width.
+    pub fn size(&self) -> Option<u16> {
         (match self {
-            DataType::Int32 => mem::size_of::<i32>(),
-            DataType::Float32 => mem::size_of::<f32>(),
-        } as u16)
+            DataType::Int8 => Some(mem::size_of::<i8>()),
+            DataType::Int16 => Some(mem::size_of::<i16>()),
+            DataType::Int32 => Some(mem::size_of::<i32>()),
+            DataType::Int64 => Some(mem::size_of::<i64>()),
+            DataType::Float32 => Some(mem::size_of::<f32>()),
+            DataType::Float64 => Some(mem::size_of::<f64>()),
+            DataType::Bool => Some(mem::size_of::<u8>()),
+            DataType::Utf8 => None,
+        })
+        .map(|size| size as u16)
+    }
+
+    /// The on-disk type tag used by the container format.
+    pub fn tag(&self) -> u8 {
+        match self {
+            DataType::Int8 => 0,
+            DataType::Int16 => 1,
+            DataType::Int32 => 2,
+            DataType::Int64 => 3,
+            DataType::Float32 => 4,
+            DataType::Float64 => 5,
+            DataType::Bool => 6,
+            DataType::Utf8 => 7,
+        }
+    }
+
+    pub fn from_tag(tag: u8) -> Option<DataType> {
+        match tag {
+            0 => Some(DataType::Int8),
+            1 => Some(DataType::Int16),
+            2 => Some(DataType::Int32),
+            3 => Some(DataType::Int64),
+            4 => Some(DataType::Float32),
+            5 => Some(DataType::Float64),
+            6 => Some(DataType::Bool),
+            7 => Some(DataType::Utf8),
+            _ => None,
+        }
+    }
+
+    /// Reads one fixed-width value out of `bytes`, which must be exactly
+    /// `size()` bytes of little-endian, in the container's packed layout.
+    /// Not valid for `Utf8`; its values live in an offsets + byte region
+    /// instead, read via `container::ColumnReaders::get_str`.
+    pub fn read_le_bytes(&self, bytes: &[u8]) -> Data {
+        match self {
+            DataType::Int8 => Data::Int8(bytes[0] as i8),
+            DataType::Int16 => Data::Int16(i16::from_le_bytes([bytes[0], bytes[1]])),
+            DataType::Int32 => Data::Int32(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+            DataType::Int64 => Data::Int64(i64::from_le_bytes([
+                bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
+            ])),
+            DataType::Float32 => Data::Float32(f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+            DataType::Float64 => Data::Float64(f64::from_le_bytes([
+                bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
+            ])),
+            DataType::Bool => Data::Bool(bytes[0] != 0),
+            DataType::Utf8 => unreachable!("Utf8 values are read via their offsets array, not read_le_bytes"),
+        }
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Data {
-    Float32(f32),
+    Int8(i8),
+    Int16(i16),
     Int32(i32),
+    Int64(i64),
+    Float32(f32),
+    Float64(f64),
+    Bool(bool),
+    Utf8(String),
+}
+
+impl Data {
+    pub fn write<'o>(&self, output: &'o mut [u8]) -> Result<&'o mut [u8], ParseError> {
+        match self {
+            Data::Int8(value) => value.write(output),
+            Data::Int16(value) => value.write(output),
+            Data::Int32(value) => value.write(output),
+            Data::Int64(value) => value.write(output),
+            Data::Float32(value) => value.write(output),
+            Data::Float64(value) => value.write(output),
+            Data::Bool(value) => value.write(output),
+            Data::Utf8(value) => value.write(output),
+        }
+    }
+
+    /// Packs the value into its fixed-width, little-endian on-disk
+    /// representation, as used by the container format's value regions.
+    /// Panics for `Utf8`, which the container writes via an offsets array
+    /// instead of this fixed-width path.
+    pub fn to_le_bytes(&self) -> Vec<u8> {
+        match self {
+            Data::Int8(value) => value.to_le_bytes().to_vec(),
+            Data::Int16(value) => value.to_le_bytes().to_vec(),
+            Data::Int32(value) => value.to_le_bytes().to_vec(),
+            Data::Int64(value) => value.to_le_bytes().to_vec(),
+            Data::Float32(value) => value.to_le_bytes().to_vec(),
+            Data::Float64(value) => value.to_le_bytes().to_vec(),
+            Data::Bool(value) => vec![*value as u8],
+            Data::Utf8(_) => unreachable!("Utf8 columns are packed via their offsets array, not to_le_bytes"),
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -32,87 +139,170 @@ pub struct Column {
 }
 
 pub mod literals {
-    pub const FLOAT32: &str = "Float32";
+    pub const INT8: &str = "Int8";
+    pub const INT16: &str = "Int16";
     pub const INT32: &str = "Int32";
+    pub const INT64: &str = "Int64";
+    pub const FLOAT32: &str = "Float32";
+    pub const FLOAT64: &str = "Float64";
+    pub const BOOL: &str = "Bool";
+    pub const UTF8: &str = "Utf8";
 
     pub const NULLABLE: &str = "Nullable";
     pub const SEPARATOR: u8 = b',';
 }
 
-const LIT_TYPES: [(&str, DataType); 2] = [
-    (literals::FLOAT32, DataType::Float32),
+const LIT_TYPES: [(&str, DataType); 8] = [
+    (literals::INT8, DataType::Int8),
+    (literals::INT16, DataType::Int16),
     (literals::INT32, DataType::Int32),
+    (literals::INT64, DataType::Int64),
+    (literals::FLOAT32, DataType::Float32),
+    (literals::FLOAT64, DataType::Float64),
+    (literals::BOOL, DataType::Bool),
+    (literals::UTF8, DataType::Utf8),
 ];
 
+/// A value failed to parse or format. Carries no location of its own;
+/// callers that know the surrounding record, column and schema offset wrap
+/// this into a full `KbtError` with that context attached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseError;
+
+impl Error for ParseError {
+    fn description(&self) -> &str {
+        "value did not parse"
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        None
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "value did not parse")
+    }
+}
+
 pub trait Parse {
-    // TODO change the error type AND str parameter
-    fn parse(bytes: &str) -> Result<Self, KbtError> where Self: Sized;
-    fn write<'o>(&self, output: &'o mut [u8]) -> Result<&'o mut [u8], KbtError>;
+    fn parse(bytes: &str) -> Result<Self, ParseError> where Self: Sized;
+    fn write<'o>(&self, output: &'o mut [u8]) -> Result<&'o mut [u8], ParseError>;
+}
+
+macro_rules! impl_parse_itoa {
+    ($ty:ty) => {
+        impl Parse for $ty {
+            fn parse(bytes: &str) -> Result<$ty, ParseError> {
+                bytes.parse::<$ty>().map_err(|_| ParseError)
+            }
+
+            fn write<'o>(&self, output: &'o mut [u8]) -> Result<&'o mut [u8], ParseError> {
+                let bytes = itoa::write(&mut *output, *self).map_err(|_| ParseError)?;
+                let remainder = &mut output[bytes..];
+                Ok(remainder)
+            }
+        }
+    };
+}
+
+impl_parse_itoa!(i8);
+impl_parse_itoa!(i16);
+impl_parse_itoa!(i32);
+impl_parse_itoa!(i64);
+
+macro_rules! impl_parse_dtoa {
+    ($ty:ty) => {
+        impl Parse for $ty {
+            fn parse(bytes: &str) -> Result<$ty, ParseError> {
+                <$ty>::from_str(bytes).map_err(|_| ParseError)
+            }
+
+            fn write<'o>(&self, output: &'o mut [u8]) -> Result<&'o mut [u8], ParseError> {
+                let bytes = dtoa::write(&mut *output, *self).map_err(|_| ParseError)?;
+                let remainder = &mut output[bytes..];
+                Ok(remainder)
+            }
+        }
+    };
 }
 
-impl Parse for i32 {
-    fn parse(bytes: &str) -> Result<i32, KbtError> {
-        i32::from_str_radix(bytes, 10).map_err(|_| KbtError)
+impl_parse_dtoa!(f32);
+impl_parse_dtoa!(f64);
+
+impl Parse for bool {
+    fn parse(bytes: &str) -> Result<bool, ParseError> {
+        bool::from_str(bytes).map_err(|_| ParseError)
     }
 
-    fn write<'o>(&self, output: &'o mut [u8]) -> Result<&'o mut [u8], KbtError> {
-        // TODO change the error type
-        let bytes =
-            itoa::write(&mut *output, *self).map_err(|_| KbtError)?;
-        let remainder = &mut output[bytes..];
-        Ok(remainder)
+    fn write<'o>(&self, output: &'o mut [u8]) -> Result<&'o mut [u8], ParseError> {
+        let text = if *self { b"true".as_ref() } else { b"false".as_ref() };
+        if output.len() < text.len() {
+            return Err(ParseError);
+        }
+        output[..text.len()].copy_from_slice(text);
+        Ok(&mut output[text.len()..])
     }
 }
 
-impl Parse for f32 {
-    fn parse(bytes: &str) -> Result<f32, KbtError> {
-        f32::from_str(bytes).map_err(|_| KbtError)
+impl Parse for String {
+    fn parse(bytes: &str) -> Result<String, ParseError> {
+        Ok(bytes.to_string())
     }
 
-    fn write<'o>(&self, output: &'o mut [u8]) -> Result<&'o mut [u8], KbtError> {
-        // TODO change the error type
-        let bytes = dtoa::write(&mut *output, *self).map_err(|_| KbtError)?;
-
-        let remainder = &mut output[bytes..];
-        Ok(remainder)
+    fn write<'o>(&self, output: &'o mut [u8]) -> Result<&'o mut [u8], ParseError> {
+        let bytes = self.as_bytes();
+        if output.len() < bytes.len() {
+            return Err(ParseError);
+        }
+        output[..bytes.len()].copy_from_slice(bytes);
+        Ok(&mut output[bytes.len()..])
     }
 }
 
 
 impl Column {
 
-    pub fn parse_data(&self, field: &str) -> Result<Data, Box<Error>> {
+    pub fn parse_data(&self, field: &str) -> Result<Data, ParseError> {
         Ok(match self.dtype {
-            DataType::Float32 => Data::Float32(f32::parse(field)?),
+            DataType::Int8 => Data::Int8(i8::parse(field)?),
+            DataType::Int16 => Data::Int16(i16::parse(field)?),
             DataType::Int32 => Data::Int32(i32::parse(field)?),
+            DataType::Int64 => Data::Int64(i64::parse(field)?),
+            DataType::Float32 => Data::Float32(f32::parse(field)?),
+            DataType::Float64 => Data::Float64(f64::parse(field)?),
+            DataType::Bool => Data::Bool(bool::parse(field)?),
+            DataType::Utf8 => Data::Utf8(String::parse(field)?),
         })
     }
 
     fn parse_single_datatype<'a, 'b>(string: &'a str, literal: &str, datatype: DataType) -> Option<(DataType, &'a str)> {
         if string.starts_with(literal) {
-            Some((datatype, string[literal.len()..].trim_left()))
+            Some((datatype, string[literal.len()..].trim_start()))
         } else {
             None
         }
     }
 
-    fn parse_datatype(string: &str) -> Result<(DataType, &str), KbtError> {
+    // Returns the leftover slice at the point of failure, not the original
+    // `string`, so the caller can turn it into a precise byte offset.
+    fn parse_datatype(string: &str) -> Result<(DataType, &str), &str> {
         (&LIT_TYPES[..]).iter().filter_map(|(literal, datatype)|
                 Column::parse_single_datatype(string, literal, *datatype)
             )
             .find(|_| true)
-            .ok_or(KbtError)
+            .ok_or(string)
     }
 
     fn parse_nullable(string: &str) -> (bool, &str) {
         if string.starts_with(literals::NULLABLE) {
-            (true, string[literals::NULLABLE.len()..].trim_left())
+            (true, string[literals::NULLABLE.len()..].trim_start())
         } else {
-            (false, string.trim_left())
+            (false, string.trim_start())
         }
     }
 
-    pub fn parse(string: &str) -> Result<(Column, &str), KbtError> {
+    pub fn parse(string: &str) -> Result<(Column, &str), &str> {
 
         let (dtype, leftover) = Column::parse_datatype(string)?;
 
@@ -122,11 +312,11 @@ impl Column {
     }
 }
 
-fn parse_separator(string: &str) -> Result<&str, KbtError> {
-    if string.as_bytes()[0] == literals::SEPARATOR {
-        Ok(string[1..].trim_left())
+fn parse_separator(string: &str) -> Result<&str, &str> {
+    if string.as_bytes().get(0) == Some(&literals::SEPARATOR) {
+        Ok(string[1..].trim_start())
     } else {
-        Err(KbtError)
+        Err(string)
     }
 }
 
@@ -136,13 +326,15 @@ pub fn parse(string: &str) -> Result<Vec<Column>, KbtError> {
 
     loop {
         trace!("leftover: {:?}", leftover);
-        let result = Column::parse(leftover)?;
-        trace!("result: {:?}", result);
-        columns.push(result.0);
+        let (column, rest) = Column::parse(leftover)
+            .map_err(|at| KbtError::schema(string, at, "a data type (Int8, Int16, Int32, Int64, Float32, Float64, Bool or Utf8), optionally followed by Nullable"))?;
+        trace!("result: {:?}", (column, rest));
+        columns.push(column);
 
-        if result.1.is_empty() { break };
+        if rest.is_empty() { break };
 
-        leftover = parse_separator(result.1)?;
+        leftover = parse_separator(rest)
+            .map_err(|at| KbtError::schema(string, at, "','"))?;
     }
 
     Ok(columns)
@@ -170,6 +362,14 @@ mod tests {
             (   "  Int32   Nullable", &[
                 Column{ dtype: DataType::Int32, nullable: true }
             ]),
+            ("Int8, Int16, Int64, Float64, Bool, Utf8 Nullable", &[
+                Column{ dtype: DataType::Int8, nullable: false },
+                Column{ dtype: DataType::Int16, nullable: false },
+                Column{ dtype: DataType::Int64, nullable: false },
+                Column{ dtype: DataType::Float64, nullable: false },
+                Column{ dtype: DataType::Bool, nullable: false },
+                Column{ dtype: DataType::Utf8, nullable: true }
+            ]),
         ];
 
     #[test]
@@ -199,7 +399,7 @@ mod tests {
 
         for schema_str in INVALID_SCHEMAS {
             info!("Testing {:?}", schema_str);
-            assert_eq!(Err(KbtError), parse(schema_str));
+            assert!(parse(schema_str).is_err(), "expected {:?} to be rejected", schema_str);
         }
     }
 